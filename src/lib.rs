@@ -1,19 +1,34 @@
 //! The `Crawly` web crawler efficiently fetches and stores content from web pages.
-//! It respects `robots.txt` guidelines and handles rate limits.
+//! It respects `robots.txt` guidelines, handles rate limits, and can seed its
+//! frontier from `sitemap.xml` for broader coverage. [`Crawler::start_with`]
+//! lets callers supply their own extractor to turn pages into typed results
+//! instead of raw HTML, and streams them back as they're found rather than
+//! buffering the whole crawl in memory.
 
+use addr::parse_domain_name;
 use anyhow::Result;
-use futures::future::join_all;
+use flate2::read::GzDecoder;
+use futures::stream::{FuturesUnordered, StreamExt};
+use futures::Stream;
+use http::Extensions;
 use indexmap::IndexMap;
 pub use mime::Mime;
-use reqwest::header::HeaderValue;
-use reqwest::{Client, Url};
-use robotstxt::DefaultMatcher;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::header::{HeaderValue, CONTENT_ENCODING, RETRY_AFTER};
+use reqwest::{Client, Request, Response, StatusCode, Url};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Middleware, Next};
 use scraper::{Html, Selector};
 use std::collections::HashSet;
 use std::fmt::Debug;
+use std::io::Read;
 use std::str::FromStr;
-use tokio::sync::{RwLock, Semaphore};
-use tokio::time::{sleep, Duration};
+use std::sync::Arc;
+use std::time::SystemTime;
+use texting_robots::Robot;
+use tokio::sync::{mpsc, Mutex, OwnedSemaphorePermit, RwLock, Semaphore};
+use tokio::time::{sleep, Duration, Instant};
+use tokio_stream::wrappers::ReceiverStream;
 
 const USER_AGENT: &str = "CrawlyRustCrawler";
 
@@ -22,12 +37,189 @@ const MAX_DEPTH: usize = 5;
 const MAX_PAGES: usize = 15;
 const MAX_CONCURRENT_REQUESTS: usize = 1_000;
 const RATE_LIMIT_WAIT_SECONDS: u64 = 1;
+const DEFAULT_MAX_RETRIES: u32 = 0; // Retries are opt-in via `with_retry`.
+const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
 
-/// Cache structure to store information about a domain's `robots.txt`.
-#[derive(Debug)]
+/// Cache entry holding a domain's parsed, group-matched `robots.txt`.
 struct RobotsCache {
-    content: String,
-    crawl_delay: Option<u64>, // Delay specified by the `robots.txt`.
+    robot: Robot,         // Parsed rules for `config.user_agent` (falling back to `*`).
+    crawl_delay: u64,     // `Crawl-delay` from the matched group, or the configured default.
+    sitemaps: Vec<Url>,   // `Sitemap:` directives, used to seed the sitemap-based crawl.
+}
+
+/// The shape of a parsed sitemap document.
+enum SitemapContent {
+    /// A `<urlset>`: page URLs to enqueue directly.
+    UrlSet(Vec<Url>),
+    /// A `<sitemapindex>`: child sitemap URLs that must be fetched and
+    /// parsed recursively.
+    Index(Vec<Url>),
+}
+
+/// Host-scope state for a single `start` invocation: the root host plus the
+/// domain allow/block lists and subdomain/TLD settings from `CrawlerConfig`.
+struct CrawlScope<'a> {
+    root_host: String,
+    config: &'a CrawlerConfig,
+}
+
+/// State that travels unchanged through every recursive `crawl` call for a
+/// single `start`/`start_with` invocation, bundled together so `crawl` itself
+/// doesn't need to take each one as its own parameter.
+struct CrawlContext<'a, T, F> {
+    visited: &'a RwLock<HashSet<Url>>,
+    tx: &'a mpsc::Sender<(Url, T)>,
+    scope: &'a CrawlScope<'a>,
+    scrape: &'a F,
+}
+
+impl CrawlScope<'_> {
+    /// Whether `url`'s host is in scope for this crawl.
+    fn allows(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+
+        if self
+            .config
+            .blocked_domains
+            .iter()
+            .any(|blocked| self.host_matches(host, blocked))
+        {
+            return false;
+        }
+
+        if !self.config.allowed_domains.is_empty() {
+            return self.host_matches(host, &self.root_host)
+                || self
+                    .config
+                    .allowed_domains
+                    .iter()
+                    .any(|allowed| self.host_matches(host, allowed));
+        }
+
+        self.host_matches(host, &self.root_host)
+    }
+
+    /// Whether `host` is considered the same scope as `target`, per the
+    /// configured subdomain and TLD modes.
+    fn host_matches(&self, host: &str, target: &str) -> bool {
+        if host.eq_ignore_ascii_case(target) {
+            return true;
+        }
+
+        if self.config.subdomains
+            && host
+                .to_ascii_lowercase()
+                .ends_with(&format!(".{}", target.to_ascii_lowercase()))
+        {
+            return true;
+        }
+
+        if self.config.tld {
+            if let (Ok(host_domain), Ok(target_domain)) =
+                (parse_domain_name(host), parse_domain_name(target))
+            {
+                if let (Some(host_root), Some(target_root)) =
+                    (host_domain.root(), target_domain.root())
+                {
+                    // `root()` is the registrable domain (e.g. `example.com`);
+                    // its first label is what stays the same across TLDs.
+                    let host_label = host_root.split('.').next().unwrap_or_default();
+                    let target_label = target_root.split('.').next().unwrap_or_default();
+                    return host_label.eq_ignore_ascii_case(target_label);
+                }
+            }
+        }
+
+        false
+    }
+}
+
+/// Reduces `host` to its registrable domain (eTLD+1, e.g. `blog.example.com`
+/// -> `example.com`), falling back to `host` itself if it can't be parsed as
+/// a domain name. Used to key per-site state (politeness scheduling,
+/// per-host concurrency) so subdomains of the same site share it, rather
+/// than the exact host used for `robots_cache` (robots.txt is per-host).
+fn registrable_domain(host: &str) -> String {
+    parse_domain_name(host)
+        .ok()
+        .and_then(|domain| domain.root().map(str::to_string))
+        .unwrap_or_else(|| host.to_string())
+}
+
+/// Retries connection errors and `429`/`5xx` responses with exponential
+/// backoff, honoring a `Retry-After` header when the server sends one.
+struct RetryMiddleware {
+    max_retries: u32,
+    base_delay: Duration,
+}
+
+#[async_trait::async_trait]
+impl Middleware for RetryMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let mut attempt = 0;
+
+        loop {
+            let Some(cloned_req) = req.try_clone() else {
+                return next.run(req, extensions).await;
+            };
+
+            let result = next.clone().run(cloned_req, extensions).await;
+
+            let should_retry = match &result {
+                Ok(response) => {
+                    response.status() == StatusCode::TOO_MANY_REQUESTS
+                        || response.status().is_server_error()
+                }
+                Err(reqwest_middleware::Error::Reqwest(error)) => {
+                    error.is_connect() || error.is_timeout()
+                }
+                Err(_) => false,
+            };
+
+            if !should_retry || attempt >= self.max_retries {
+                return result;
+            }
+
+            let delay = result
+                .as_ref()
+                .ok()
+                .and_then(|response| response.headers().get(RETRY_AFTER))
+                .and_then(retry_after_delay)
+                .unwrap_or_else(|| {
+                    // `attempt` is bounded by `max_retries`, which callers may set
+                    // arbitrarily high, so the exponent and the multiply are both
+                    // saturating to avoid overflow instead of panicking or
+                    // silently wrapping to a bogus tiny delay.
+                    let backoff = 2u32.checked_pow(attempt).unwrap_or(u32::MAX);
+                    self.base_delay.checked_mul(backoff).unwrap_or(Duration::MAX)
+                });
+
+            tracing::debug!("Retrying request after {{ delay: {delay:?}, attempt: {attempt} }}.");
+
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Parses a `Retry-After` header value, which may be either a number of
+/// seconds or an HTTP-date.
+fn retry_after_delay(value: &HeaderValue) -> Option<Duration> {
+    let value = value.to_str().ok()?;
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let target = httpdate::parse_http_date(value).ok()?;
+    target.duration_since(SystemTime::now()).ok()
 }
 
 /// Configuration parameters for the `Crawler`.
@@ -40,6 +232,15 @@ struct CrawlerConfig {
     rate_limit_wait_seconds: u64,
     robots: bool,
     allowed_mimes: Vec<Mime>,
+    sitemap: bool,
+    proxy: Option<Url>,
+    max_retries: u32,
+    retry_base_delay: Duration,
+    allowed_domains: Vec<String>,
+    blocked_domains: Vec<String>,
+    subdomains: bool,
+    tld: bool,
+    max_requests_per_host: Option<usize>,
 }
 
 impl Default for CrawlerConfig {
@@ -53,6 +254,15 @@ impl Default for CrawlerConfig {
             rate_limit_wait_seconds: RATE_LIMIT_WAIT_SECONDS,
             robots: true,
             allowed_mimes: vec![],
+            sitemap: false,
+            proxy: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_base_delay: DEFAULT_RETRY_BASE_DELAY,
+            allowed_domains: vec![],
+            blocked_domains: vec![],
+            subdomains: false,
+            tld: false,
+            max_requests_per_host: None,
         }
     }
 }
@@ -118,6 +328,66 @@ impl CrawlerBuilder {
         self
     }
 
+    /// Seed the crawl frontier from `sitemap.xml` (and any `Sitemap:`
+    /// directives in `robots.txt`) before recursing from the root URL.
+    pub fn with_sitemap(mut self, sitemap: bool) -> Self {
+        self.config.sitemap = sitemap;
+        self
+    }
+
+    /// Route all requests through the given proxy.
+    pub fn with_proxy(mut self, proxy: Url) -> Self {
+        self.config.proxy = Some(proxy);
+        self
+    }
+
+    /// Retry connection errors and `429`/`5xx` responses with exponential
+    /// backoff, honoring a `Retry-After` header when the server sends one.
+    /// `base_delay` is the delay before the first retry and doubles on each
+    /// subsequent attempt.
+    pub fn with_retry(mut self, max_retries: u32, base_delay: Duration) -> Self {
+        self.config.max_retries = max_retries;
+        self.config.retry_base_delay = base_delay;
+        self
+    }
+
+    /// Restrict crawling to these domains, in addition to the root host.
+    /// When empty (the default), only the root host (subject to
+    /// `with_subdomains`/`with_tld`) is in scope.
+    pub fn with_allowed_domains(mut self, allowed_domains: Vec<String>) -> Self {
+        self.config.allowed_domains = allowed_domains;
+        self
+    }
+
+    /// Never crawl these domains, even if they would otherwise be allowed.
+    /// Blocklist entries always win over the allowlist and the root host.
+    pub fn with_blocked_domains(mut self, blocked_domains: Vec<String>) -> Self {
+        self.config.blocked_domains = blocked_domains;
+        self
+    }
+
+    /// When `true`, allow any subdomain of an in-scope domain (e.g.
+    /// `blog.example.com` when `example.com` is in scope).
+    pub fn with_subdomains(mut self, subdomains: bool) -> Self {
+        self.config.subdomains = subdomains;
+        self
+    }
+
+    /// When `true`, allow the same registrable domain across TLDs (e.g.
+    /// `example.org` when `example.com` is in scope).
+    pub fn with_tld(mut self, tld: bool) -> Self {
+        self.config.tld = tld;
+        self
+    }
+
+    /// Cap concurrent in-flight requests to a single host, layered under the
+    /// global `with_max_concurrent_requests` cap, so multi-domain crawls
+    /// stay polite to each site without limiting overall throughput.
+    pub fn with_max_requests_per_host(mut self, max_requests_per_host: usize) -> Self {
+        self.config.max_requests_per_host = Some(max_requests_per_host);
+        self
+    }
+
     /// Consumes the builder and returns a configured `Crawler` instance.
     pub fn build(self) -> Result<Crawler> {
         Crawler::from_config(self.config)
@@ -126,19 +396,36 @@ impl CrawlerBuilder {
 
 /// Main structure for the `Crawler` containing necessary utilities and caches.
 pub struct Crawler {
-    config: CrawlerConfig, // Configuration parameters.
-    client: Client,        // HTTP client to make web requests.
-    robots_cache: RwLock<IndexMap<String, RobotsCache>>, // Cache for `robots.txt` per domain.
+    config: CrawlerConfig,        // Configuration parameters.
+    client: ClientWithMiddleware, // HTTP client (with retry/proxy middleware) to make web requests.
+    robots_cache: RwLock<IndexMap<String, RobotsCache>>, // Cache for `robots.txt` per host.
+    host_schedules: RwLock<IndexMap<String, Arc<Mutex<Option<Instant>>>>>, // Last-request time per registrable domain, guarded so check+sleep+update is atomic.
+    host_semaphores: RwLock<IndexMap<String, Arc<Semaphore>>>, // Per-registrable-domain concurrency caps.
 }
 
 impl Crawler {
     /// Initializes the crawler with a given configuration.
     fn from_config(config: CrawlerConfig) -> Result<Self> {
+        let mut client_builder = Client::builder().user_agent(config.user_agent.as_str());
+
+        if let Some(proxy) = &config.proxy {
+            client_builder = client_builder.proxy(reqwest::Proxy::all(proxy.clone())?);
+        }
+
+        let mut middleware_builder = ClientBuilder::new(client_builder.build()?);
+
+        if config.max_retries > 0 {
+            middleware_builder = middleware_builder.with(RetryMiddleware {
+                max_retries: config.max_retries,
+                base_delay: config.retry_base_delay,
+            });
+        }
+
         Ok(Self {
-            client: Client::builder()
-                .user_agent(config.user_agent.as_str())
-                .build()?,
+            client: middleware_builder.build(),
             robots_cache: RwLock::new(IndexMap::new()),
+            host_schedules: RwLock::new(IndexMap::new()),
+            host_semaphores: RwLock::new(IndexMap::new()),
             config,
         })
     }
@@ -149,20 +436,23 @@ impl Crawler {
     }
 
     /// Asynchronously crawls a URL. Honors `robots.txt`, maintains state about visited URLs,
-    /// and manages rate limits and concurrency.
+    /// manages rate limits and concurrency, and streams each scraped page to `tx` as it's found.
     #[async_recursion::async_recursion]
-    #[tracing::instrument(skip(self, semaphore, visited, content))]
-    async fn crawl(
+    #[tracing::instrument(skip(self, semaphore, ctx))]
+    async fn crawl<T, F>(
         &self,
         semaphore: &Semaphore, // Rate limiting and concurrency management.
         url: Url,
-        depth: usize,                            // Current depth of the crawl.
-        visited: &RwLock<HashSet<Url>>,          // Set of visited URLs to avoid redundancy.
-        content: &RwLock<IndexMap<Url, String>>, // Collected content per URL.
-    ) -> Result<()> {
+        depth: usize, // Current depth of the crawl.
+        ctx: &CrawlContext<'_, T, F>,
+    ) -> Result<()>
+    where
+        T: Send,
+        F: Fn(&Url, &Html, &str) -> Option<T> + Sync,
+    {
         // Recursion base cases.
         {
-            let visited_read = visited.read().await;
+            let visited_read = ctx.visited.read().await;
             if depth > self.config.max_depth
                 || visited_read.len() >= self.config.max_pages
                 || visited_read.contains(&url)
@@ -178,78 +468,39 @@ impl Crawler {
 
         let permit = semaphore.acquire().await;
 
+        let domain = url.domain().unwrap_or_default().to_string();
+        // `robots_cache` is keyed by the exact host (robots.txt is per-host),
+        // but per-site scheduling/concurrency should apply across subdomains
+        // of the same site, so those are keyed by registrable domain instead.
+        let site = registrable_domain(&domain);
+        let host_permit = self.acquire_host_permit(&site).await;
+
         // Robots.txt handling logic
-        if self.config.robots {
-            // Fetch and handle `robots.txt` for the domain.
-            let robots_url = format!(
-                "{}://{}/robots.txt",
-                url.scheme(),
-                url.host().ok_or(anyhow::anyhow!("Host not found."))?
-            );
-            let domain = url.domain().unwrap_or_default().to_string();
-
-            let mut robots_cache = self.robots_cache.write().await;
-
-            // Get cached robots info or fetch if not cached.
-            let robots = if let Some(info) = robots_cache.get(&domain) {
-                tracing::debug!(
-                    "Cache found for robots.txt {{ robots_cache: {robots_cache:#?} }}."
-                );
+        let delay_seconds = if self.config.robots {
+            self.ensure_robots_cached(&domain, &url).await;
 
-                Some((
-                    info.content.clone(),
-                    info.crawl_delay.unwrap_or(RATE_LIMIT_WAIT_SECONDS),
-                ))
-            } else if let Ok(response) = self.client.get(&robots_url).send().await {
-                let robots_content = response.text().await?;
-
-                tracing::debug!("Cache not found for robots.txt, fetched a new one {{ robots_content: {robots_content} }}.");
-
-                let delay_seconds = robots_content
-                    .lines()
-                    .filter_map(|line| {
-                        if line.contains("Crawl-delay") {
-                            line.split(':').last()?.trim().parse().ok()
-                        } else {
-                            None
-                        }
-                    })
-                    .next()
-                    .unwrap_or(RATE_LIMIT_WAIT_SECONDS);
-
-                robots_cache.insert(
-                    domain.clone(),
-                    RobotsCache {
-                        content: robots_content.clone(),
-                        crawl_delay: Some(delay_seconds),
-                    },
-                );
+            let (allowed, delay_seconds) = {
+                let robots_cache = self.robots_cache.read().await;
+                let cached = robots_cache
+                    .get(&domain)
+                    .expect("ensure_robots_cached just inserted this domain");
 
-                Some((robots_content, delay_seconds))
-            } else {
-                None
+                (cached.robot.allowed(url.as_str()), cached.crawl_delay)
             };
 
-            drop(robots_cache);
-
-            if let Some((robots_content, delay_seconds)) = robots {
-                tracing::debug!("Sleeping for {delay_seconds} due to robots.txt policies...");
-
-                // Respect the crawl delay specified by `robots.txt`.
-                sleep(Duration::from_secs(delay_seconds)).await;
-
-                // Check permission from `robots.txt` before proceeding.
-                if !DefaultMatcher::default().one_agent_allowed_by_robots(
-                    &robots_content,
-                    self.config.user_agent.as_str(),
-                    url.as_str(),
-                ) {
-                    return Ok(());
-                }
+            if !allowed {
+                return Ok(());
             }
+
+            delay_seconds
         } else {
-            sleep(Duration::from_secs(self.config.rate_limit_wait_seconds)).await;
-        }
+            self.config.rate_limit_wait_seconds
+        };
+
+        // Sleep only for whatever's left of this host's own crawl delay since its
+        // last request, rather than a blanket delay before every request.
+        self.wait_for_host_turn(&site, Duration::from_secs(delay_seconds))
+            .await;
 
         let response = self.client.get(url.clone()).send().await?;
 
@@ -274,60 +525,72 @@ impl Crawler {
                 })
                 .unwrap_or(true)
         {
-            // Explicitly dropping the permit to free up concurrency slot.
+            // Explicitly dropping the permits to free up concurrency slots.
             drop(permit);
+            drop(host_permit);
 
-            visited.write().await.insert(url.clone());
+            ctx.visited.write().await.insert(url.clone());
 
             return Ok(());
         }
 
-        // Fetch the page content.
+        // Parse the page once and pull out everything we need from it before the
+        // next `.await`: `scraper::Html` is not `Send`, and this function's
+        // future must be (it's boxed by `#[async_recursion]` and driven via
+        // `tokio::spawn`), so the parsed document can't be held across an await.
         let url_content = String::from_utf8(page)?;
-        content
-            .write()
-            .await
-            .insert(url.clone(), url_content.clone());
+        let (extracted, links) = {
+            let document = Html::parse_document(&url_content);
+            let extracted = (ctx.scrape)(&url, &document, &url_content);
+            let links = Self::extract_links(&document)?;
+            (extracted, links)
+        };
 
-        // Explicitly dropping the permit to free up concurrency slot.
+        tracing::debug!(
+            "Found other sub-URLs {{ len: {}, links: {links:#?} }}",
+            links.len()
+        );
+
+        if let Some(extracted) = extracted {
+            // The receiver may have been dropped if the caller stopped consuming the
+            // stream early; that's not an error for the crawl itself.
+            let _ = ctx.tx.send((url.clone(), extracted)).await;
+        }
+
+        // Explicitly dropping the permits to free up concurrency slots.
         drop(permit);
+        drop(host_permit);
 
         {
-            let mut visited_write = visited.write().await;
+            let mut visited_write = ctx.visited.write().await;
             visited_write.insert(url.clone());
             if visited_write.len() >= self.config.max_pages {
                 return Ok(());
             }
         }
 
-        // Continue crawling by processing extracted links recursively.
-        let _ = join_all(
-            Self::extract_links(url_content.as_str())
-                .map(|links| {
-                    tracing::debug!(
-                        "Found other sub-URLs {{ len: {}, links: {links:#?} }}",
-                        links.len()
-                    );
-
-                    links
-                })?
-                .into_iter()
-                .filter_map(|link| match url.join(&link) {
-                    Ok(url) => Some(self.crawl(semaphore, url, depth + 1, visited, content)),
-                    Err(_) => None,
-                }),
-        )
-        .await;
+        // Continue crawling by processing extracted links recursively, driving the
+        // child futures as they complete instead of waiting on the whole batch in order.
+        let mut children: FuturesUnordered<_> = links
+            .into_iter()
+            .filter_map(|link| match url.join(&link) {
+                Ok(child_url) if ctx.scope.allows(&child_url) => {
+                    Some(self.crawl(semaphore, child_url, depth + 1, ctx))
+                }
+                _ => None,
+            })
+            .collect();
+
+        while children.next().await.is_some() {}
 
         tracing::debug!("Finished crawling URL {{ url: {url} }}");
 
         Ok(())
     }
 
-    /// Extracts hyperlinks from given HTML content.
-    #[tracing::instrument(skip(content))]
-    fn extract_links(content: &str) -> Result<Vec<String>> {
-        let document = Html::parse_document(content);
+    /// Extracts hyperlinks from an already-parsed HTML document.
+    #[tracing::instrument(skip(document))]
+    fn extract_links(document: &Html) -> Result<Vec<String>> {
         let selector = Selector::parse("a").map_err(|error| anyhow::anyhow!("{:?}", error))?;
 
         Ok(document
@@ -336,20 +599,426 @@ impl Crawler {
             .collect())
     }
 
-    /// Initiates the crawling process from a specified root URL.
+    /// Initiates the crawling process from a specified root URL, streaming
+    /// each page's HTML back as it's fetched rather than buffering the
+    /// whole crawl in memory.
+    ///
+    /// Requires `Arc<Crawler>` because the crawl runs on a background task
+    /// so the returned stream can be consumed while it's still in progress.
+    pub fn start<S>(self: Arc<Self>, url: S) -> impl Stream<Item = (Url, String)>
+    where
+        S: AsRef<str> + Debug + Send + 'static,
+    {
+        self.start_with(url, |_url, _document, raw| Some(raw.to_string()))
+    }
+
+    /// Initiates the crawling process from a specified root URL, invoking
+    /// `scrape` on each successfully fetched page's raw and parsed HTML to
+    /// produce a typed result and streaming `(Url, T)` pairs back as they're
+    /// found; pages for which `scrape` returns `None` are not emitted. The
+    /// same parsed `Html` is reused for `scrape` and for link discovery, so
+    /// pages are never parsed twice.
+    ///
+    /// Requires `Arc<Crawler>` because the crawl runs on a background task
+    /// so the returned stream can be consumed while it's still in progress;
+    /// the channel's bounded capacity provides natural backpressure, so a
+    /// slow consumer caps crawl memory rather than letting it run unbounded.
+    pub fn start_with<S, T, F>(self: Arc<Self>, url: S, scrape: F) -> impl Stream<Item = (Url, T)>
+    where
+        S: AsRef<str> + Debug + Send + 'static,
+        T: Send + 'static,
+        F: Fn(&Url, &Html, &str) -> Option<T> + Sync + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(self.config.max_concurrent_requests.max(1));
+
+        tokio::spawn(async move {
+            let root_url = match Url::parse(url.as_ref()) {
+                Ok(root_url) => root_url,
+                Err(error) => {
+                    tracing::debug!("Failed to parse root URL {{ url: {url:?}, error: {error} }}.");
+                    return;
+                }
+            };
+
+            let semaphore = Semaphore::new(self.config.max_concurrent_requests);
+            let visited = RwLock::new(HashSet::new());
+            let scope = CrawlScope {
+                root_host: root_url.host_str().unwrap_or_default().to_string(),
+                config: &self.config,
+            };
+            let ctx = CrawlContext {
+                visited: &visited,
+                tx: &tx,
+                scope: &scope,
+                scrape: &scrape,
+            };
+
+            if self.config.sitemap {
+                for sitemap_url in self.discover_sitemap_urls(&root_url, &scope).await {
+                    if !scope.allows(&sitemap_url) {
+                        continue;
+                    }
+
+                    let _ = self.crawl(&semaphore, sitemap_url, 0, &ctx).await;
+                }
+            }
+
+            let _ = self.crawl(&semaphore, root_url, 0, &ctx).await;
+        });
+
+        ReceiverStream::new(rx)
+    }
+
+    /// Discovers page URLs from `sitemap.xml` (and any sitemap indexes it
+    /// references), bounded by `max_pages`. Sitemap URLs (both root
+    /// candidates and index children) are checked against `scope` before
+    /// they're fetched, since a sitemap index or a robots.txt `Sitemap:`
+    /// directive can point anywhere.
+    #[tracing::instrument(skip(self, scope))]
+    async fn discover_sitemap_urls(&self, root: &Url, scope: &CrawlScope<'_>) -> Vec<Url> {
+        let mut seeds = Vec::new();
+        let mut seen = HashSet::new();
+        let mut queue: Vec<Url> = self
+            .root_sitemap_candidates(root)
+            .await
+            .into_iter()
+            .filter(|candidate| scope.allows(candidate))
+            .collect();
+
+        while let Some(sitemap_url) = queue.pop() {
+            if seeds.len() >= self.config.max_pages || !seen.insert(sitemap_url.clone()) {
+                continue;
+            }
+
+            match self.fetch_sitemap(&sitemap_url).await {
+                Ok(SitemapContent::UrlSet(urls)) => seeds.extend(urls),
+                Ok(SitemapContent::Index(children)) => {
+                    queue.extend(children.into_iter().filter(|child| scope.allows(child)));
+                }
+                Err(error) => tracing::debug!(
+                    "Failed to fetch sitemap {{ sitemap_url: {sitemap_url}, error: {error} }}."
+                ),
+            }
+        }
+
+        seeds
+    }
+
+    /// Builds the initial set of sitemap URLs to try: the conventional
+    /// `/sitemap.xml` plus any `Sitemap:` directives parsed from `robots.txt`.
+    async fn root_sitemap_candidates(&self, root: &Url) -> Vec<Url> {
+        let mut candidates = Vec::new();
+
+        if let Ok(default_sitemap) = root.join("/sitemap.xml") {
+            candidates.push(default_sitemap);
+        }
+
+        if self.config.robots {
+            let domain = root.domain().unwrap_or_default().to_string();
+            self.ensure_robots_cached(&domain, root).await;
+
+            let robots_cache = self.robots_cache.read().await;
+            if let Some(cached) = robots_cache.get(&domain) {
+                candidates.extend(cached.sitemaps.iter().cloned());
+            }
+        }
+
+        candidates
+    }
+
+    /// Sleeps only for whatever remains of `delay` since `site`'s last
+    /// request, rather than a blanket delay before every request, so a slow
+    /// site doesn't throttle requests to fast ones. `site` is a registrable
+    /// domain, so subdomains of the same site share a schedule.
     ///
-    /// Returns a map of visited URLs and their corresponding HTML content.
-    #[tracing::instrument(skip(self))]
-    pub async fn start<S: AsRef<str> + Debug>(&self, url: S) -> Result<IndexMap<Url, String>> {
-        let root_url = Url::parse(url.as_ref())?;
+    /// The check, sleep, and update all happen while holding `site`'s
+    /// schedule lock, so concurrent same-site callers queue on it instead of
+    /// racing to read the same stale last-request time.
+    async fn wait_for_host_turn(&self, site: &str, delay: Duration) {
+        let schedule = self.host_schedule(site).await;
+        let mut last_request = schedule.lock().await;
+
+        let remaining = last_request.and_then(|last| delay.checked_sub(last.elapsed()));
+
+        if let Some(remaining) = remaining {
+            tracing::debug!("Sleeping for {remaining:?} to stay polite to {site}...");
+            sleep(remaining).await;
+        }
+
+        *last_request = Some(Instant::now());
+    }
+
+    /// Returns `site`'s schedule lock, creating it on first use.
+    async fn host_schedule(&self, site: &str) -> Arc<Mutex<Option<Instant>>> {
+        let existing = self.host_schedules.read().await.get(site).cloned();
+
+        match existing {
+            Some(schedule) => schedule,
+            None => self
+                .host_schedules
+                .write()
+                .await
+                .entry(site.to_string())
+                .or_insert_with(|| Arc::new(Mutex::new(None)))
+                .clone(),
+        }
+    }
+
+    /// Acquires a permit from `site`'s concurrency semaphore, creating it on
+    /// first use. Returns `None` (no limit) when `max_requests_per_host`
+    /// isn't configured. `site` is a registrable domain, so subdomains of
+    /// the same site share a semaphore.
+    async fn acquire_host_permit(&self, site: &str) -> Option<OwnedSemaphorePermit> {
+        let max_requests_per_host = self.config.max_requests_per_host?;
+
+        let existing = self.host_semaphores.read().await.get(site).cloned();
+
+        let semaphore = match existing {
+            Some(semaphore) => semaphore,
+            None => self
+                .host_semaphores
+                .write()
+                .await
+                .entry(site.to_string())
+                .or_insert_with(|| Arc::new(Semaphore::new(max_requests_per_host)))
+                .clone(),
+        };
+
+        semaphore.acquire_owned().await.ok()
+    }
+
+    /// Ensures `robots_cache` has a parsed, group-matched `robots.txt` entry
+    /// for `domain`, fetching it over the network if it isn't cached yet.
+    /// A missing or unfetchable `robots.txt` is treated as "allow all" with
+    /// the configured default delay.
+    async fn ensure_robots_cached(&self, domain: &str, origin: &Url) {
+        {
+            let robots_cache = self.robots_cache.read().await;
+            if robots_cache.contains_key(domain) {
+                tracing::debug!("Cache found for robots.txt {{ domain: {domain} }}.");
+                return;
+            }
+        }
+
+        // Fetch outside the lock: this is a network round-trip and must not
+        // block other tasks' robots_cache lookups for its duration.
+        let robots_url = format!(
+            "{}://{}/robots.txt",
+            origin.scheme(),
+            origin.host().map(|host| host.to_string()).unwrap_or_default()
+        );
+
+        let body = match self.client.get(&robots_url).send().await {
+            Ok(response) => response.bytes().await.unwrap_or_default().to_vec(),
+            Err(_) => Vec::new(),
+        };
+
+        tracing::debug!("Cache not found for robots.txt, fetched a new one {{ domain: {domain} }}.");
+
+        let robot = Robot::new(self.config.user_agent.as_str(), &body).unwrap_or_else(|_| {
+            Robot::new(self.config.user_agent.as_str(), b"")
+                .expect("an empty robots.txt always parses")
+        });
 
-        let semaphore = Semaphore::new(self.config.max_concurrent_requests);
-        let visited = RwLock::new(HashSet::new());
-        let content = RwLock::new(IndexMap::new());
+        let crawl_delay = robot
+            .delay
+            .map(|seconds| seconds.ceil() as u64)
+            .unwrap_or(self.config.rate_limit_wait_seconds);
 
-        self.crawl(&semaphore, root_url, 0, &visited, &content)
-            .await?;
+        let sitemaps = robot
+            .sitemaps
+            .iter()
+            .filter_map(|sitemap| Url::parse(sitemap).ok())
+            .collect();
+
+        self.robots_cache.write().await.insert(
+            domain.to_string(),
+            RobotsCache {
+                robot,
+                crawl_delay,
+                sitemaps,
+            },
+        );
+    }
+
+    /// Fetches and decodes a single sitemap document, decompressing gzip
+    /// payloads (`Content-Encoding: gzip` or a `.xml.gz` suffix) before
+    /// parsing.
+    async fn fetch_sitemap(&self, sitemap_url: &Url) -> Result<SitemapContent> {
+        let response = self.client.get(sitemap_url.clone()).send().await?;
+
+        let is_gzipped = response
+            .headers()
+            .get(CONTENT_ENCODING)
+            .map(|value| value.as_bytes() == b"gzip")
+            .unwrap_or(false)
+            || sitemap_url.path().ends_with(".xml.gz");
+
+        let bytes = response.bytes().await?;
+
+        let body = if is_gzipped {
+            let mut decompressed = String::new();
+            GzDecoder::new(bytes.as_ref()).read_to_string(&mut decompressed)?;
+            decompressed
+        } else {
+            String::from_utf8(bytes.to_vec())?
+        };
+
+        Self::parse_sitemap(&body)
+    }
+
+    /// Parses a sitemap document, distinguishing a `<urlset>` (page URLs)
+    /// from a `<sitemapindex>` (child sitemap URLs to fetch recursively).
+    fn parse_sitemap(body: &str) -> Result<SitemapContent> {
+        let mut reader = Reader::from_str(body);
+        reader.trim_text(true);
+
+        let mut is_index = false;
+        let mut in_loc = false;
+        let mut urls = Vec::new();
+        let mut buf = Vec::new();
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(tag) if tag.name().as_ref() == b"sitemapindex" => is_index = true,
+                Event::Start(tag) if tag.name().as_ref() == b"loc" => in_loc = true,
+                Event::End(tag) if tag.name().as_ref() == b"loc" => in_loc = false,
+                Event::Text(text) if in_loc => {
+                    if let Ok(url) = Url::parse(&text.unescape()?) {
+                        urls.push(url);
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        Ok(if is_index {
+            SitemapContent::Index(urls)
+        } else {
+            SitemapContent::UrlSet(urls)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(config: &CrawlerConfig, root_host: &str) -> CrawlScope<'_> {
+        CrawlScope {
+            root_host: root_host.to_string(),
+            config,
+        }
+    }
+
+    #[test]
+    fn allows_root_host_by_default() {
+        let config = CrawlerConfig::default();
+        let scope = scope(&config, "example.com");
+
+        assert!(scope.allows(&Url::parse("https://example.com/page").unwrap()));
+        assert!(!scope.allows(&Url::parse("https://other.com/page").unwrap()));
+    }
+
+    #[test]
+    fn allows_root_host_alongside_allowed_domains() {
+        let config = CrawlerConfig {
+            allowed_domains: vec!["cdn.example.com".to_string()],
+            ..CrawlerConfig::default()
+        };
+        let scope = scope(&config, "example.com");
+
+        assert!(scope.allows(&Url::parse("https://example.com/page").unwrap()));
+        assert!(scope.allows(&Url::parse("https://cdn.example.com/page").unwrap()));
+        assert!(!scope.allows(&Url::parse("https://other.com/page").unwrap()));
+    }
+
+    #[test]
+    fn blocked_domains_win_over_allowed_domains_and_root_host() {
+        let config = CrawlerConfig {
+            allowed_domains: vec!["example.com".to_string()],
+            blocked_domains: vec!["example.com".to_string()],
+            ..CrawlerConfig::default()
+        };
+        let scope = scope(&config, "example.com");
+
+        assert!(!scope.allows(&Url::parse("https://example.com/page").unwrap()));
+    }
+
+    #[test]
+    fn subdomains_require_with_subdomains() {
+        let config = CrawlerConfig::default();
+        let scope = scope(&config, "example.com");
+        assert!(!scope.allows(&Url::parse("https://blog.example.com/page").unwrap()));
+
+        let config = CrawlerConfig {
+            subdomains: true,
+            ..CrawlerConfig::default()
+        };
+        let scope = scope(&config, "example.com");
+        assert!(scope.allows(&Url::parse("https://blog.example.com/page").unwrap()));
+    }
+
+    #[test]
+    fn tld_matches_same_registrable_domain_across_tlds() {
+        let config = CrawlerConfig {
+            tld: true,
+            ..CrawlerConfig::default()
+        };
+        let scope = scope(&config, "example.com");
+
+        assert!(scope.allows(&Url::parse("https://example.org/page").unwrap()));
+        assert!(!scope.allows(&Url::parse("https://other.org/page").unwrap()));
+    }
+
+    #[test]
+    fn parses_urlset() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <url><loc>https://example.com/a</loc></url>
+                <url><loc>https://example.com/b</loc></url>
+            </urlset>"#;
+
+        match Crawler::parse_sitemap(body).unwrap() {
+            SitemapContent::UrlSet(urls) => {
+                assert_eq!(
+                    urls,
+                    vec![
+                        Url::parse("https://example.com/a").unwrap(),
+                        Url::parse("https://example.com/b").unwrap(),
+                    ]
+                );
+            }
+            SitemapContent::Index(_) => panic!("expected a urlset"),
+        }
+    }
+
+    #[test]
+    fn parses_sitemapindex() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <sitemap><loc>https://example.com/sitemap-a.xml</loc></sitemap>
+            </sitemapindex>"#;
+
+        match Crawler::parse_sitemap(body).unwrap() {
+            SitemapContent::Index(urls) => {
+                assert_eq!(urls, vec![Url::parse("https://example.com/sitemap-a.xml").unwrap()]);
+            }
+            SitemapContent::UrlSet(_) => panic!("expected a sitemapindex"),
+        }
+    }
+
+    #[test]
+    fn retry_after_delay_parses_seconds() {
+        let value = HeaderValue::from_static("120");
+        assert_eq!(retry_after_delay(&value), Some(Duration::from_secs(120)));
+    }
 
-        Ok(content.into_inner())
+    #[test]
+    fn retry_after_delay_rejects_garbage() {
+        let value = HeaderValue::from_static("not-a-delay");
+        assert_eq!(retry_after_delay(&value), None);
     }
 }